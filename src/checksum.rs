@@ -0,0 +1,21 @@
+use std::{error::Error, fs, io::Read};
+
+use sha2::{Digest, Sha256};
+
+/// Streams `path` through SHA-256 and returns the digest as a lowercase hex
+/// string, without holding the whole file in memory.
+pub(crate) fn digest_file(path: &str) -> Result<String, Box<dyn Error>> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 8192];
+
+    loop {
+        let bytes_read = file.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
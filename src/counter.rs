@@ -0,0 +1,49 @@
+use std::{collections::HashMap, fs, os::unix::fs::MetadataExt};
+
+/// Tracks one source file's hard-link group: the path we'll copy from, and
+/// every other path on disk that shares its inode and still needs to be
+/// replaced with a symlink.
+pub(crate) struct SourceCounter {
+    pub(crate) path: String,
+    pub(crate) inode: u64,
+    pub(crate) num_other_links: u64,
+    pub(crate) paths_other_links: Vec<String>,
+    /// Where `path` should be copied to, when that differs from the shared
+    /// CLI `destination` argument. Set while mirroring a recursive source
+    /// directory, where every file gets its own destination path.
+    pub(crate) destination: Option<String>,
+}
+
+impl SourceCounter {
+    pub(crate) fn new(path: String, inode: u64, num_other_links: u64) -> Self {
+        SourceCounter {
+            path,
+            inode,
+            num_other_links,
+            paths_other_links: vec![],
+            destination: None,
+        }
+    }
+
+    pub(crate) fn new_by_stat(path: String, stat: &fs::Metadata) -> Self {
+        // The file itself counts as one of its own links.
+        SourceCounter::new(path, stat.ino(), stat.nlink() - 1)
+    }
+
+    pub(crate) fn get_remaning_other_links(&self) -> u64 {
+        self.num_other_links - self.paths_other_links.len() as u64
+    }
+
+    pub(crate) fn add_path_other_link(&mut self, path: String) {
+        if self.paths_other_links.contains(&path) || self.path == path {
+            return;
+        }
+        self.paths_other_links.push(path);
+    }
+
+    pub(crate) fn is_all_links_found(&self) -> bool {
+        self.get_remaning_other_links() == 0
+    }
+}
+
+pub(crate) type INodeCounterMap = HashMap<u64, SourceCounter>;
@@ -0,0 +1,227 @@
+use std::{
+    error::Error,
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+};
+
+use crate::counter::SourceCounter;
+
+const JOURNAL_PATH: &str = ".cpwln.journal";
+
+/// Escapes `\`, the `\t` field separator, and the `,` link separator in
+/// `field` so a path containing any of them round-trips through a journal
+/// line intact instead of being mis-split on read.
+fn escape_field(field: &str) -> String {
+    let mut escaped = String::with_capacity(field.len());
+    for c in field.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            ',' => escaped.push_str("\\,"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Reverses [`escape_field`].
+fn unescape_field(field: &str) -> String {
+    let mut unescaped = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(escaped) = chars.next() {
+                unescaped.push(match escaped {
+                    't' => '\t',
+                    other => other,
+                });
+            }
+            continue;
+        }
+        unescaped.push(c);
+    }
+    unescaped
+}
+
+/// Splits `field` on `,` the way [`escape_field`] expects: a `,` that was
+/// escaped as `\,` does not end the current part.
+fn split_escaped_list(field: &str) -> Vec<String> {
+    if field.is_empty() {
+        return vec![];
+    }
+
+    let mut parts = vec![];
+    let mut current = String::new();
+    let mut chars = field.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                current.push(c);
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+            }
+            ',' => parts.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+    }
+    parts.push(current);
+
+    parts.iter().map(|part| unescape_field(part)).collect()
+}
+
+/// One `move_counter` unit recorded before it starts, so a run interrupted
+/// mid-unit leaves enough behind to finish it on the next invocation instead
+/// of losing the source file.
+pub(crate) struct JournalEntry {
+    pub(crate) inode: u64,
+    pub(crate) source: String,
+    pub(crate) destination: String,
+    pub(crate) links: Vec<String>,
+}
+
+/// Write-ahead log of in-flight `move_counter` units.
+///
+/// A unit is recorded with [`Journal::begin`] before any filesystem mutation
+/// happens, and marked with [`Journal::done`] once every link for it has been
+/// replaced with a symlink. Anything left without a matching `done` after a
+/// crash is replayed with `--resume`.
+pub(crate) struct Journal {
+    file: File,
+}
+
+impl Journal {
+    pub(crate) fn open() -> Result<Self, Box<dyn Error>> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(JOURNAL_PATH)?;
+
+        Ok(Journal { file })
+    }
+
+    /// Records `links` as the links this unit will symlink, not
+    /// `counter.paths_other_links` wholesale: callers that filter the
+    /// discovered links (e.g. `--limit`) must pass the already-filtered list,
+    /// so `--resume` replays exactly what the interrupted run meant to do.
+    pub(crate) fn begin(
+        &mut self,
+        counter: &SourceCounter,
+        destination: &str,
+        links: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        let links = links
+            .iter()
+            .map(|link| escape_field(link))
+            .collect::<Vec<_>>()
+            .join(",");
+        writeln!(
+            self.file,
+            "BEGIN\t{}\t{}\t{}\t{}",
+            counter.inode,
+            escape_field(&counter.path),
+            escape_field(destination),
+            links
+        )?;
+        self.file.sync_all()?;
+
+        Ok(())
+    }
+
+    pub(crate) fn done(&mut self, inode: u64) -> Result<(), Box<dyn Error>> {
+        writeln!(self.file, "DONE\t{inode}")?;
+        self.file.sync_all()?;
+
+        Ok(())
+    }
+
+    /// Removes the journal once every unit recorded in it has completed.
+    pub(crate) fn clear() -> Result<(), Box<dyn Error>> {
+        if fs::metadata(JOURNAL_PATH).is_ok() {
+            fs::remove_file(JOURNAL_PATH)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads back the journal left by a previous run and returns the units that
+/// were started but never marked done.
+pub(crate) fn read_incomplete() -> Result<Vec<JournalEntry>, Box<dyn Error>> {
+    if fs::metadata(JOURNAL_PATH).is_err() {
+        return Ok(vec![]);
+    }
+
+    let reader = BufReader::new(File::open(JOURNAL_PATH)?);
+    let mut pending: Vec<JournalEntry> = vec![];
+
+    for line in reader.lines() {
+        let line = line?;
+        let mut parts = line.splitn(5, '\t');
+        let Some(kind) = parts.next() else { continue };
+
+        match kind {
+            "BEGIN" => {
+                let inode: u64 = parts.next().unwrap_or_default().parse()?;
+                let source = unescape_field(parts.next().unwrap_or_default());
+                let destination = unescape_field(parts.next().unwrap_or_default());
+                let links = split_escaped_list(parts.next().unwrap_or_default());
+
+                pending.push(JournalEntry {
+                    inode,
+                    source,
+                    destination,
+                    links,
+                });
+            }
+            "DONE" => {
+                let inode: u64 = parts.next().unwrap_or_default().parse()?;
+                pending.retain(|entry| entry.inode != inode);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(pending)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Regression test: a comma or tab in a path used to shift the
+    /// tab-delimited fields or mis-split the comma-joined link list on
+    /// replay. Round-trips paths containing both through `begin` and
+    /// `read_incomplete`.
+    #[test]
+    fn round_trips_paths_containing_tab_and_comma() {
+        let dir = std::env::temp_dir().join(format!("cpwln-journal-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let original_dir = std::env::current_dir().unwrap();
+        std::env::set_current_dir(&dir).unwrap();
+
+        let counter = SourceCounter::new("notes, final.txt".to_string(), 42, 2);
+        let links = vec![
+            "link\twith\ttab.txt".to_string(),
+            "link,with,comma.txt".to_string(),
+        ];
+
+        let result = (|| -> Result<Vec<JournalEntry>, Box<dyn Error>> {
+            let mut journal = Journal::open()?;
+            journal.begin(&counter, "dest\tpath,here", &links)?;
+            read_incomplete()
+        })();
+
+        std::env::set_current_dir(original_dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let pending = result.unwrap();
+        assert_eq!(pending.len(), 1);
+        let entry = &pending[0];
+        assert_eq!(entry.inode, 42);
+        assert_eq!(entry.source, "notes, final.txt");
+        assert_eq!(entry.destination, "dest\tpath,here");
+        assert_eq!(entry.links, links);
+    }
+}
@@ -0,0 +1,259 @@
+use std::{error::Error, fs, fs::File, io, os::unix::fs::symlink, path::Path};
+
+use relative_path::RelativePath;
+
+use crate::{checksum::digest_file, counter::SourceCounter, journal::Journal, journal::JournalEntry};
+
+/// Whether `path` is `limit` itself or lives somewhere under it, so
+/// `--limit` can be given either a single discovered link's exact path or a
+/// directory prefix covering many of them.
+pub(crate) fn is_within_limit(path: &str, limit: &str) -> bool {
+    let path = Path::new(path);
+    let limit = Path::new(limit);
+
+    path == limit || path.starts_with(limit)
+}
+
+/// Replaces destination file with a symlink to the source file
+/// If the destination is a directory, it will symlink the source file into the directory
+pub(crate) fn replace_with_symlink(source: &str, destination: &str) -> Result<(), std::io::Error> {
+    // If the destination is a directory
+    let destination_metadata = fs::metadata(destination)?;
+
+    let relative_source = RelativePath::new(source);
+
+    if destination_metadata.is_dir() {
+        let relative_destination_dir = RelativePath::new(destination);
+        let relative_destination =
+            relative_destination_dir.join(relative_source.file_name().unwrap());
+
+        let relative_path_object = relative_destination_dir.relative(relative_source);
+        let relative_path = relative_path_object.to_string();
+        let destination = relative_destination.to_string();
+
+        // FIXME: Make work cross platform
+        return symlink(relative_path, destination);
+    }
+
+    fs::remove_file(destination)?;
+
+    let relative_destination = RelativePath::new(destination);
+    let relative_destination_dir = relative_destination.parent().unwrap();
+
+    let relative_path_object = relative_destination_dir.relative(relative_source);
+    let relative_path = relative_path_object.to_string();
+    let destination = relative_destination.to_string();
+
+    // FIXME: Make work cross platform
+    symlink(relative_path, destination)
+}
+
+/// Copies `source` to `destination` crash-safely: the content lands at a
+/// temporary name next to `destination` first, is fsynced, and only then is
+/// atomically renamed into place, so a destination path never exists
+/// half-written.
+pub(crate) fn copy_atomically(source: &str, destination: &str) -> Result<(), Box<dyn Error>> {
+    let temp_destination = format!("{destination}.cpwln-tmp");
+
+    fs::copy(source, &temp_destination)?;
+    File::open(&temp_destination)?.sync_all()?;
+    fs::rename(&temp_destination, destination)?;
+
+    Ok(())
+}
+
+/// Works out where `source.path` should end up: its own per-file destination
+/// if one was set while mirroring a recursive source directory, otherwise
+/// the shared CLI `destination`, with the source's file name appended when
+/// that destination is an existing directory.
+pub(crate) fn resolve_destination(source: &SourceCounter, destination: &str) -> String {
+    match &source.destination {
+        Some(overridden) => overridden.clone(),
+        None => match fs::metadata(destination) {
+            Ok(metadata) => {
+                if metadata.is_dir() {
+                    let relative_source = RelativePath::new(&source.path);
+                    RelativePath::new(destination)
+                        .join(relative_source.file_name().unwrap())
+                        .to_string()
+                } else {
+                    destination.to_string()
+                }
+            }
+            Err(_) => destination.to_string(),
+        },
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub(crate) fn move_counter(
+    source: SourceCounter,
+    destination: &str,
+    journal: &mut Journal,
+    verify: bool,
+    limit: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let destination_file_path = resolve_destination(&source, destination);
+    let destination_str = destination_file_path.as_str();
+
+    let effective_links: Vec<String> = source
+        .paths_other_links
+        .iter()
+        .filter(|path| limit.is_none_or(|limit| is_within_limit(path, limit)))
+        .cloned()
+        .collect();
+
+    journal.begin(&source, destination_str, &effective_links)?;
+
+    let source_digest = if verify {
+        Some(digest_file(source.path.as_str())?)
+    } else {
+        None
+    };
+
+    copy_atomically(source.path.as_str(), destination_str)?;
+
+    if let Some(source_digest) = source_digest {
+        let destination_digest = digest_file(destination_str)?;
+        if destination_digest != source_digest {
+            fs::remove_file(destination_str)?;
+            return Err(Box::new(io::Error::other(format!(
+                "Checksum mismatch copying {} to {destination_str}, original left untouched",
+                source.path
+            ))));
+        }
+    }
+
+    replace_with_symlink(destination_str, source.path.as_str())?;
+
+    for path in &effective_links {
+        replace_with_symlink(destination_str, path.as_str())?;
+    }
+
+    journal.done(source.inode)?;
+
+    Ok(())
+}
+
+/// Finishes a unit left behind by an interrupted run. Idempotent: if the
+/// copy already landed at `entry.destination` (rename already happened) it
+/// is not redone, and re-running `replace_with_symlink` on a path that is
+/// already a symlink just recreates it pointing at the same place. Applies
+/// the same checksum verification `move_counter` applies to a fresh copy, so
+/// `--verify` isn't silently dropped on the resume path.
+pub(crate) fn replay_entry(entry: &JournalEntry, verify: bool) -> Result<(), Box<dyn Error>> {
+    if fs::metadata(&entry.destination).is_err() {
+        copy_atomically(&entry.source, &entry.destination)?;
+    }
+
+    if verify {
+        let source_digest = digest_file(&entry.source)?;
+        let destination_digest = digest_file(&entry.destination)?;
+        if destination_digest != source_digest {
+            fs::remove_file(&entry.destination)?;
+            return Err(Box::new(io::Error::other(format!(
+                "Checksum mismatch resuming copy of {} to {}, original left untouched",
+                entry.source, entry.destination
+            ))));
+        }
+    }
+
+    replace_with_symlink(&entry.destination, &entry.source)?;
+
+    for link in &entry.links {
+        replace_with_symlink(&entry.destination, link)?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn ensure_dir(path: &str) -> Result<(), Box<dyn Error>> {
+    let metadata_option = fs::metadata(path);
+    if metadata_option.is_err() {
+        fs::create_dir_all(path)?;
+
+        return Ok(());
+    }
+
+    let metadata = metadata_option.unwrap();
+
+    if !metadata.is_dir() {
+        fs::remove_file(path)?;
+        fs::create_dir_all(path)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Regression test: `replay_entry` used to skip the checksum check
+    /// entirely, so a destination left corrupt by whatever interrupted the
+    /// previous run would get symlinked to instead of rejected.
+    #[test]
+    fn replay_entry_rejects_mismatched_destination_and_leaves_source_untouched() {
+        let dir = std::env::temp_dir().join(format!("cpwln-linker-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("source.txt");
+        let destination = dir.join("dest.txt");
+        fs::write(&source, b"hello").unwrap();
+        fs::write(&destination, b"corrupted").unwrap();
+
+        let entry = JournalEntry {
+            inode: 1,
+            source: source.to_string_lossy().to_string(),
+            destination: destination.to_string_lossy().to_string(),
+            links: vec![],
+        };
+
+        let result = replay_entry(&entry, true);
+        let destination_removed = fs::metadata(&destination).is_err();
+        let source_untouched =
+            fs::read(&source).is_ok_and(|contents| contents == b"hello");
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(result.is_err());
+        assert!(destination_removed);
+        assert!(source_untouched);
+    }
+
+    /// Regression test: `--limit` must leave out-of-scope hard links as real
+    /// hard links, the same way `store_counter` honors it under `--store`.
+    #[test]
+    fn leaves_links_outside_limit_as_hard_links() {
+        let dir = std::env::temp_dir().join(format!("cpwln-linker-limit-test-{}", std::process::id()));
+        let src_dir = dir.join("src");
+        let keep_dir = src_dir.join("keep");
+        let skip_dir = src_dir.join("skip");
+        fs::create_dir_all(&keep_dir).unwrap();
+        fs::create_dir_all(&skip_dir).unwrap();
+        fs::write(src_dir.join("a.txt"), b"hello").unwrap();
+        fs::hard_link(src_dir.join("a.txt"), keep_dir.join("a_keep.txt")).unwrap();
+        fs::hard_link(src_dir.join("a.txt"), skip_dir.join("a_skip.txt")).unwrap();
+
+        let original_dir = std::env::current_dir().unwrap();
+        std::env::set_current_dir(&dir).unwrap();
+
+        let mut counter = SourceCounter::new("src/a.txt".to_string(), 1, 2);
+        counter.add_path_other_link("src/keep/a_keep.txt".to_string());
+        counter.add_path_other_link("src/skip/a_skip.txt".to_string());
+
+        let mut journal = Journal::open().unwrap();
+        let result = move_counter(counter, "dest.txt", &mut journal, true, Some("src/keep"));
+
+        let skip_is_hard_link = fs::symlink_metadata("src/skip/a_skip.txt")
+            .is_ok_and(|metadata| !metadata.file_type().is_symlink());
+        let keep_is_symlink = fs::symlink_metadata("src/keep/a_keep.txt")
+            .is_ok_and(|metadata| metadata.file_type().is_symlink());
+
+        std::env::set_current_dir(original_dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        result.unwrap();
+        assert!(skip_is_hard_link, "link outside --limit should stay a hard link");
+        assert!(keep_is_symlink, "link inside --limit should become a symlink");
+    }
+}
@@ -1,163 +1,50 @@
+mod checksum;
+mod counter;
+mod journal;
+mod linker;
+mod search;
+mod status;
+mod store;
+mod walk;
+
 use clap::{arg, command, Command};
-use glob::glob;
 use relative_path::RelativePath;
 use std::{
     collections::HashMap,
-    error::Error,
-    fs::{self, Metadata},
-    io,
-    os::unix::fs::{symlink, MetadataExt},
+    fs, io,
+    os::unix::fs::MetadataExt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
 };
 
-struct SourceCounter {
-    path: String,
-    inode: u64,
-    num_other_links: u64,
-    paths_other_links: Vec<String>,
-}
-
-impl SourceCounter {
-    fn new(path: String, inode: u64, num_other_links: u64) -> Self {
-        SourceCounter {
-            path,
-            inode,
-            num_other_links,
-            paths_other_links: vec![],
-        }
-    }
-
-    fn new_by_stat(path: String, stat: &fs::Metadata) -> Self {
-        SourceCounter {
-            path,
-            inode: stat.ino(),
-            num_other_links: stat.nlink(),
-            paths_other_links: vec![],
-        }
-    }
-
-    fn get_remaning_other_links(&self) -> u64 {
-        self.num_other_links - self.paths_other_links.len() as u64
-    }
-
-    fn add_path_other_link(&mut self, path: String) {
-        if self.paths_other_links.contains(&path) || self.path == path {
-            return;
-        }
-        self.paths_other_links.push(path);
-    }
-
-    fn is_all_links_found(&self) -> bool {
-        self.get_remaning_other_links() == 0
-    }
-}
+use counter::{INodeCounterMap, SourceCounter};
+use journal::Journal;
+use linker::{ensure_dir, move_counter, replay_entry};
+use search::search_and_count;
 
 fn cli() -> Command {
     command!()
         .arg(arg!(<search> "A glob pattern for where to search for the links"))
         .arg(arg!(<source> ... "The source file or directory to copy."))
         .arg(arg!(<destination> "The destination file or directory to copy to."))
-}
-
-type INodeCounterMap = HashMap<u64, SourceCounter>;
-
-fn search_and_count(
-    search: &str,
-    mut counters: INodeCounterMap,
-) -> Result<INodeCounterMap, Box<dyn Error>> {
-    for entry in glob(search).expect("Failed to read glob pattern") {
-        let entry = entry?;
-        let metadata = entry.metadata()?;
-        let inode = metadata.ino();
-
-        if let Some(counter) = counters.get_mut(&inode) {
-            counter.add_path_other_link(entry.to_string_lossy().to_string());
-        }
-    }
-
-    Ok(counters)
-}
-
-/// Replaces destination file with a symlink to the source file
-/// If the destination is a directory, it will symlink the source file into the directory
-fn replace_with_symlink(source: &str, destination: &str) -> Result<(), std::io::Error> {
-    // If the destination is a directory
-    let destination_metadata = fs::metadata(destination)?;
-
-    let relative_source = RelativePath::new(source);
-
-    if destination_metadata.is_dir() {
-        let relative_destination_dir = RelativePath::new(destination);
-        let relative_destination =
-            relative_destination_dir.join(relative_source.file_name().unwrap());
-
-        let relative_path_object = relative_destination_dir.relative(relative_source);
-        let relative_path = relative_path_object.to_string();
-        let destination = relative_destination.to_string();
-
-        // FIXME: Make work cross platform
-        return symlink(relative_path, destination);
-    }
-
-    fs::remove_file(destination)?;
-
-    let relative_destination = RelativePath::new(destination);
-    let relative_destination_dir = relative_destination.parent().unwrap();
-
-    let relative_path_object = relative_destination_dir.relative(relative_source);
-    let relative_path = relative_path_object.to_string();
-    let destination = relative_destination.to_string();
-
-    // FIXME: Make work cross platform
-    symlink(relative_path, destination)
-}
-
-#[allow(clippy::needless_pass_by_value)]
-fn move_counter(source: SourceCounter, destination: &str) -> Result<(), Box<dyn Error>> {
-    let destination_file_path = match fs::metadata(destination) {
-        Ok(metadata) => {
-            if metadata.is_dir() {
-                let destination_dir_str = destination;
-                let relative_source = RelativePath::new(&source.path);
-                let destination = RelativePath::new(destination_dir_str)
-                    .join(relative_source.file_name().unwrap());
-                destination.to_string()
-            } else {
-                destination.to_string()
-            }
-        }
-        Err(_) => destination.to_string(),
-    };
-    let destination_str = destination_file_path.as_str();
-    let copy_result = fs::copy(source.path.as_str(), destination_str);
-    if let Err(err) = copy_result {
-        return Err(Box::new(err));
-    }
-
-    replace_with_symlink(destination_str, source.path.as_str())?;
-
-    for path in source.paths_other_links {
-        replace_with_symlink(destination_str, path.as_str())?;
-    }
-
-    Ok(())
-}
-
-fn ensure_dir(path: &str) -> Result<(), Box<dyn Error>> {
-    let metadata_option = fs::metadata(path);
-    if metadata_option.is_err() {
-        fs::create_dir_all(path)?;
-
-        return Ok(());
-    }
-
-    let metadata = metadata_option.unwrap();
-
-    if !metadata.is_dir() {
-        fs::remove_file(path)?;
-        fs::create_dir_all(path)?;
-    }
-
-    Ok(())
+        .arg(arg!(--resume "Replay a journal left behind by an interrupted run, then continue"))
+        .arg(
+            arg!(--verify "Verify copy integrity by checksum before replacing the original (default)")
+                .conflicts_with("no-verify"),
+        )
+        .arg(arg!(--"no-verify" "Skip checksum verification of copies"))
+        .arg(arg!(--store "Treat destination as a content-addressed store, deduplicating by content instead of just hard links"))
+        .arg(
+            arg!(-j --jobs <N> "Number of threads to use when searching for links")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(arg!(--"dry-run" "Report what would be copied and symlinked without touching the filesystem"))
+        .arg(
+            arg!(--"keep-going" "Process every fully-resolved source even if some sources are missing links or unreadable"),
+        )
+        .arg(arg!(-l --limit <PATH> "Only replace discovered links at this exact path, or anywhere under it"))
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -172,74 +59,131 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let destination = matches
         .get_one::<String>("destination")
         .expect("No destination provided.");
+    let resume = matches.get_flag("resume");
+    let verify = !matches.get_flag("no-verify");
+    let store_mode = matches.get_flag("store");
+    let jobs = matches.get_one::<usize>("jobs").copied();
+    let dry_run = matches.get_flag("dry-run");
+    let keep_going = matches.get_flag("keep-going");
+    let limit = matches.get_one::<String>("limit").map(String::as_str);
+
+    let source_paths: Vec<&String> = source.collect();
+    let is_multiple_sources = source_paths.len() > 1;
+
+    if is_multiple_sources && !dry_run {
+        ensure_dir(destination)?;
+    }
 
-    // Stat source files
-    // And check if the "file" is a directory, if it is a directory, it is not support for now
-    let source_files: Vec<Result<(&String, Metadata), _>> = source
-        .map(|s| {
-            let metadata = fs::metadata(s).expect("Failed to read metadata");
-            if metadata.is_dir() {
-                // panic!("Directories are not supported yet.");
-                return Err("Directories are not supported yet.");
-            }
+    let mut counter_map: INodeCounterMap = HashMap::new();
+    let mut bad: Vec<(String, String)> = vec![];
 
-            if !metadata.is_file() {
-                return Err("Only files are supported at the moment");
+    for path in &source_paths {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                bad.push(((*path).to_string(), err.to_string()));
+                continue;
             }
+        };
 
-            Ok((s, metadata))
-        })
-        .collect();
+        if metadata.is_dir() {
+            let dir_name = RelativePath::new(path)
+                .file_name()
+                .expect("Source directory has no name");
+            let mirrored_destination = RelativePath::new(destination).join(dir_name).to_string();
 
-    if let Some(err) = source_files.iter().find(|x| x.is_err()) {
-        return Err(Box::new(io::Error::new(
-            io::ErrorKind::Other,
-            err.to_owned().unwrap_err(),
-        )));
+            counter_map =
+                walk::walk_and_count(path, &mirrored_destination, counter_map, dry_run)?;
+            continue;
+        }
+
+        if !metadata.is_file() {
+            bad.push((
+                (*path).to_string(),
+                "Only files and directories are supported at the moment".to_string(),
+            ));
+            continue;
+        }
+
+        let inode = metadata.ino();
+        // The file itself is also a link, so to find other links, we need to subtract one
+        let num_other_links = metadata.nlink() - 1;
+        counter_map.insert(
+            inode,
+            SourceCounter::new((*path).to_string(), inode, num_other_links),
+        );
     }
 
-    let source_files_unwrapped: Vec<&(&String, Metadata)> =
-        source_files.iter().map(|x| x.as_ref().unwrap()).collect();
+    let updated_counters = search_and_count(search, counter_map, jobs)?;
+    let status = status::categorize(updated_counters, bad);
 
-    let counters = source_files_unwrapped.iter().map(|(path, s)| {
-        let inode = s.ino();
-        // The file itself is also a links, so to find other links, we need to subtract one
-        let num_other_links = s.nlink() - 1;
-        let path = (*path).to_string();
+    if dry_run {
+        if store_mode {
+            status::print_store_report(status, destination)?;
+        } else {
+            status::print_report(&status, destination, true, limit);
+        }
+        return Ok(());
+    }
 
-        SourceCounter {
-            path,
-            inode,
-            num_other_links,
-            paths_other_links: vec![],
+    if !status.is_clean() {
+        // Without --keep-going nothing gets copied, so report it as hypothetical.
+        status::print_report(&status, destination, !keep_going, limit);
+
+        if !keep_going {
+            return Err(Box::new(io::Error::other(
+                "Some sources were not fully resolved; rerun with --keep-going to process what was resolved",
+            )));
         }
-    });
+    }
 
-    let is_multiple_sources = source_files_unwrapped.len() > 1;
+    let incomplete = journal::read_incomplete()?;
+    if !incomplete.is_empty() && !resume {
+        return Err(Box::new(io::Error::other(
+            "Found a journal from an interrupted run, rerun with --resume to finish it",
+        )));
+    }
 
-    let counter_map = counters
-        .map(|c| (c.inode, c))
-        .collect::<std::collections::HashMap<u64, SourceCounter>>();
+    let mut journal = Journal::open()?;
 
-    let updated_counters = search_and_count(search, counter_map)?;
+    for entry in incomplete {
+        replay_entry(&entry, verify)?;
+        journal.done(entry.inode)?;
+    }
 
-    if updated_counters
-        .iter()
-        .any(|(_, c)| !c.is_all_links_found())
+    let interrupted = Arc::new(AtomicBool::new(false));
     {
-        return Err(Box::new(io::Error::new(
-            io::ErrorKind::Other,
-            "Not all links were found, try a broader search",
-        )));
+        let interrupted = Arc::clone(&interrupted);
+        ctrlc::set_handler(move || {
+            interrupted.store(true, Ordering::SeqCst);
+        })?;
     }
 
-    if is_multiple_sources {
-        ensure_dir(destination)?;
-    }
+    if store_mode {
+        let resolved: INodeCounterMap = status.resolved.into_iter().map(|c| (c.inode, c)).collect();
+        let (merged_counters, digests) = store::group_by_content(resolved)?;
+
+        for (inode, counter) in merged_counters {
+            let digest = &digests[&inode];
+            store::store_counter(&counter, digest, destination, &mut journal, verify, limit)?;
+
+            if interrupted.load(Ordering::SeqCst) {
+                eprintln!("Interrupted after finishing the current file; rerun with --resume to continue.");
+                return Ok(());
+            }
+        }
+    } else {
+        for counter in status.resolved {
+            move_counter(counter, destination, &mut journal, verify, limit)?;
 
-    for (_, counter) in updated_counters {
-        move_counter(counter, destination)?;
+            if interrupted.load(Ordering::SeqCst) {
+                eprintln!("Interrupted after finishing the current file; rerun with --resume to continue.");
+                return Ok(());
+            }
+        }
     }
 
+    Journal::clear()?;
+
     Ok(())
 }
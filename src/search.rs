@@ -0,0 +1,206 @@
+use std::{
+    error::Error,
+    fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
+
+use glob::{MatchOptions, Pattern};
+use rayon::ThreadPoolBuilder;
+
+use crate::counter::INodeCounterMap;
+
+/// How many directories deep the parallel walk will recurse before it stops
+/// forking further, so a pathologically deep or cyclic tree can't blow the
+/// stack.
+const MAX_DEPTH: usize = 1024;
+
+/// The directory to start walking from: everything in `pattern` up to its
+/// first wildcard character. This lets the walk start somewhere concrete
+/// without having to special-case `*`/`?`/`[...]` itself.
+///
+/// If the wildcard falls right after a path separator, the prefix is already
+/// a complete directory and becomes the root as-is (`"data/*.txt"` ->
+/// `"data"`). Otherwise the wildcard sits inside a partial path component
+/// (`"a/b*/c.txt"`), so the root is that component's parent directory
+/// (`"a/b*/c.txt"` -> `"a"`).
+fn glob_root(pattern: &str) -> PathBuf {
+    let wildcard = pattern.find(['*', '?', '[']).unwrap_or(pattern.len());
+    let prefix = &pattern[..wildcard];
+
+    if prefix.is_empty() {
+        return PathBuf::from(".");
+    }
+
+    if let Some(stripped) = prefix.strip_suffix('/') {
+        return PathBuf::from(stripped);
+    }
+
+    match Path::new(prefix).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// Renders `path` the same way the rest of the program builds path strings
+/// (CLI arguments, `SourceCounter::path`): no leading `./`. `fs::read_dir(".")`
+/// yields entries joined onto `.` as their directory, which would otherwise
+/// make every discovered link compare unequal to the literal paths the CLI
+/// registered for the same files.
+fn normalize_path_string(path: &Path) -> String {
+    let rendered = path.to_string_lossy();
+    match rendered.strip_prefix("./") {
+        Some(stripped) => stripped.to_string(),
+        None => rendered.to_string(),
+    }
+}
+
+/// Scopes `*`/`?`/`[...]` to a single path component, matching
+/// `glob::glob`'s usual behavior (`glob::Pattern::matches_path`'s defaults
+/// let them cross `/`).
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+fn walk(dir: &Path, pattern: &Pattern, sender: &mpsc::Sender<(u64, String)>, depth: usize) {
+    if depth >= MAX_DEPTH {
+        return;
+    }
+
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    rayon::scope(|scope| {
+        for entry in entries.filter_map(Result::ok) {
+            let sender = sender.clone();
+            scope.spawn(move |_| {
+                let path = entry.path();
+                let Ok(metadata) = entry.metadata() else {
+                    return;
+                };
+
+                if metadata.is_dir() {
+                    walk(&path, pattern, &sender, depth + 1);
+                    return;
+                }
+
+                let normalized = normalize_path_string(&path);
+                if pattern.matches_path_with(&path, MATCH_OPTIONS)
+                    || pattern.matches_with(&normalized, MATCH_OPTIONS)
+                {
+                    let _ = sender.send((metadata.ino(), normalized));
+                }
+            });
+        }
+    });
+}
+
+/// Parallel equivalent of walking `search` with `glob::glob` and statting
+/// every match: forks a rayon task per subdirectory instead of using a flat
+/// work queue, which stays readable and scales naturally with how deep the
+/// tree is nested. Matches are funneled back through a channel and folded
+/// into `counters` on the calling thread. `jobs` bounds the thread pool size;
+/// `None` lets rayon pick its default (the number of CPUs).
+pub(crate) fn search_and_count(
+    search: &str,
+    mut counters: INodeCounterMap,
+    jobs: Option<usize>,
+) -> Result<INodeCounterMap, Box<dyn Error>> {
+    let pattern = Pattern::new(search)?;
+    let root = glob_root(search);
+
+    let mut pool_builder = ThreadPoolBuilder::new();
+    if let Some(jobs) = jobs {
+        pool_builder = pool_builder.num_threads(jobs);
+    }
+    let pool = pool_builder.build()?;
+
+    let (sender, receiver) = mpsc::channel();
+
+    pool.install(|| walk(&root, &pattern, &sender, 0));
+    drop(sender);
+
+    for (inode, path) in receiver {
+        if let Some(counter) = counters.get_mut(&inode) {
+            counter.add_path_other_link(path);
+        }
+    }
+
+    Ok(counters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counter::SourceCounter;
+
+    /// Regression test for a one-level directory prefix (`"data/*.txt"`):
+    /// `glob_root` used to collapse this to `"."`, and the resulting `"./"`-
+    /// prefixed paths never matched the literal `"data/..."` paths the CLI
+    /// registers, so an existing hard link was never found.
+    #[test]
+    fn finds_hard_linked_file_under_one_level_prefix() {
+        let dir = std::env::temp_dir().join(format!("cpwln-search-test-{}", std::process::id()));
+        let data_dir = dir.join("data");
+        fs::create_dir_all(&data_dir).unwrap();
+        fs::write(data_dir.join("a.txt"), b"hello").unwrap();
+        fs::hard_link(data_dir.join("a.txt"), data_dir.join("b.txt")).unwrap();
+
+        let original_dir = std::env::current_dir().unwrap();
+        std::env::set_current_dir(&dir).unwrap();
+
+        let metadata = fs::metadata("data/a.txt").unwrap();
+        let mut counters = INodeCounterMap::new();
+        counters.insert(
+            metadata.ino(),
+            SourceCounter::new("data/a.txt".to_string(), metadata.ino(), metadata.nlink() - 1),
+        );
+
+        let result = search_and_count("data/*.txt", counters, None);
+
+        std::env::set_current_dir(original_dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let counters = result.unwrap();
+        assert!(counters[&metadata.ino()].is_all_links_found());
+    }
+
+    /// Regression test for `*` crossing directory boundaries: `glob::Pattern`
+    /// doesn't scope `*` to a single path component unless
+    /// `require_literal_separator` is set, so `"data/*.txt"` used to also
+    /// match a hard link nested under `data/sub/`.
+    #[test]
+    fn does_not_match_nested_file_against_single_component_wildcard() {
+        let dir = std::env::temp_dir().join(format!("cpwln-search-test-{}", std::process::id()));
+        let data_dir = dir.join("data");
+        let sub_dir = data_dir.join("sub");
+        fs::create_dir_all(&sub_dir).unwrap();
+        fs::write(data_dir.join("a.txt"), b"hello").unwrap();
+        fs::hard_link(data_dir.join("a.txt"), data_dir.join("b.txt")).unwrap();
+        fs::hard_link(data_dir.join("a.txt"), sub_dir.join("c.txt")).unwrap();
+
+        let original_dir = std::env::current_dir().unwrap();
+        std::env::set_current_dir(&dir).unwrap();
+
+        let metadata = fs::metadata("data/a.txt").unwrap();
+        let mut counters = INodeCounterMap::new();
+        counters.insert(
+            metadata.ino(),
+            SourceCounter::new("data/a.txt".to_string(), metadata.ino(), metadata.nlink() - 1),
+        );
+
+        let result = search_and_count("data/*.txt", counters, None);
+
+        std::env::set_current_dir(original_dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let counters = result.unwrap();
+        let counter = &counters[&metadata.ino()];
+        assert_eq!(counter.paths_other_links, vec!["data/b.txt".to_string()]);
+        assert!(!counter.is_all_links_found());
+    }
+}
@@ -0,0 +1,164 @@
+use std::error::Error;
+
+use crate::{
+    counter::{INodeCounterMap, SourceCounter},
+    linker::{is_within_limit, resolve_destination},
+    store,
+};
+
+/// Buckets every discovered source by how resolvable it turned out to be,
+/// instead of bailing out on the first problem.
+pub(crate) struct ResolutionStatus {
+    /// Every hard link was found; ready to copy and symlink.
+    pub(crate) resolved: Vec<SourceCounter>,
+    /// Some, but not all, of the other hard links were found.
+    pub(crate) missing_links: Vec<SourceCounter>,
+    /// A source path that could not be stat'd or wasn't a file/directory,
+    /// paired with a description of what went wrong.
+    pub(crate) bad: Vec<(String, String)>,
+}
+
+impl ResolutionStatus {
+    pub(crate) fn is_clean(&self) -> bool {
+        self.missing_links.is_empty() && self.bad.is_empty()
+    }
+}
+
+pub(crate) fn categorize(counters: INodeCounterMap, bad: Vec<(String, String)>) -> ResolutionStatus {
+    let mut resolved = vec![];
+    let mut missing_links = vec![];
+
+    for (_, counter) in counters {
+        if counter.is_all_links_found() {
+            resolved.push(counter);
+        } else {
+            missing_links.push(counter);
+        }
+    }
+
+    ResolutionStatus {
+        resolved,
+        missing_links,
+        bad,
+    }
+}
+
+/// Prints what would happen (or is about to happen) to each bucket in
+/// `status`, without touching the filesystem. `limit`, when set, marks the
+/// other-link paths that `--limit` would leave as real hard links instead of
+/// turning into symlinks.
+pub(crate) fn print_report(
+    status: &ResolutionStatus,
+    destination: &str,
+    dry_run: bool,
+    limit: Option<&str>,
+) {
+    let verb = if dry_run { "would copy" } else { "will copy" };
+
+    if !status.resolved.is_empty() {
+        println!("resolved:");
+        for counter in &status.resolved {
+            let destination_path = resolve_destination(counter, destination);
+            println!("  {verb} {} -> {destination_path}", counter.path);
+            println!("    {} -> symlink to {destination_path}", counter.path);
+            for link in &counter.paths_other_links {
+                if limit.is_some_and(|limit| !is_within_limit(link, limit)) {
+                    println!("    {link} -> left as a hard link (outside --limit)");
+                } else {
+                    println!("    {link} -> symlink to {destination_path}");
+                }
+            }
+        }
+    }
+
+    print_unresolved(&status.missing_links, &status.bad);
+}
+
+/// `--store`'s counterpart to `print_report`: groups `status.resolved` by
+/// content digest and shows each group landing at its content-addressed
+/// object path under `destination` instead of the mirrored destination path
+/// `--store` never uses. Takes `status` by value since grouping by content
+/// has to consume `resolved`.
+pub(crate) fn print_store_report(
+    status: ResolutionStatus,
+    destination: &str,
+) -> Result<(), Box<dyn Error>> {
+    let resolved: INodeCounterMap = status
+        .resolved
+        .into_iter()
+        .map(|counter| (counter.inode, counter))
+        .collect();
+    let (merged_counters, digests) = store::group_by_content(resolved)?;
+
+    if !merged_counters.is_empty() {
+        println!("resolved:");
+        for (inode, counter) in &merged_counters {
+            let object = store::object_path(destination, &digests[inode]);
+            println!("  would copy {} -> {object}", counter.path);
+            println!("    {} -> symlink to {object}", counter.path);
+            for link in &counter.paths_other_links {
+                println!("    {link} -> symlink to {object}");
+            }
+        }
+    }
+
+    print_unresolved(&status.missing_links, &status.bad);
+
+    Ok(())
+}
+
+fn print_unresolved(missing_links: &[SourceCounter], bad: &[(String, String)]) {
+    if !missing_links.is_empty() {
+        println!("missing_links:");
+        for counter in missing_links {
+            println!(
+                "  {} ({} of {} other links found)",
+                counter.path,
+                counter.paths_other_links.len(),
+                counter.num_other_links
+            );
+        }
+    }
+
+    if !bad.is_empty() {
+        println!("bad:");
+        for (path, error) in bad {
+            println!("  {path}: {error}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn categorize_buckets_by_resolution_and_flags_clean() {
+        let mut resolved = SourceCounter::new("resolved.txt".to_string(), 1, 1);
+        resolved.add_path_other_link("resolved_link.txt".to_string());
+        let incomplete = SourceCounter::new("incomplete.txt".to_string(), 2, 1);
+
+        let mut counters = INodeCounterMap::new();
+        counters.insert(resolved.inode, resolved);
+        counters.insert(incomplete.inode, incomplete);
+
+        let status = categorize(counters, vec![("bad.txt".to_string(), "stat failed".to_string())]);
+
+        assert_eq!(status.resolved.len(), 1);
+        assert_eq!(status.resolved[0].path, "resolved.txt");
+        assert_eq!(status.missing_links.len(), 1);
+        assert_eq!(status.missing_links[0].path, "incomplete.txt");
+        assert!(!status.is_clean());
+
+        let only_resolved = categorize(
+            {
+                let mut counters = INodeCounterMap::new();
+                let resolved = SourceCounter::new("resolved.txt".to_string(), 1, 0);
+                counters.insert(resolved.inode, resolved);
+                counters
+            },
+            vec![],
+        );
+        assert!(only_resolved.is_clean());
+    }
+}
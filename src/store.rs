@@ -0,0 +1,157 @@
+use std::{collections::HashMap, error::Error, fs, io};
+
+use crate::{
+    checksum::digest_file,
+    counter::{INodeCounterMap, SourceCounter},
+    journal::Journal,
+    linker::{copy_atomically, is_within_limit, replace_with_symlink},
+};
+
+/// Merges every `SourceCounter` in `counters` that is byte-identical (same
+/// SHA-256 digest) into a single group. Returns the merged counters together
+/// with the digest each surviving counter's content hashed to, so callers
+/// don't have to hash twice.
+pub(crate) fn group_by_content(
+    mut counters: INodeCounterMap,
+) -> Result<(INodeCounterMap, HashMap<u64, String>), Box<dyn Error>> {
+    let mut canonical_inode_for_digest: HashMap<String, u64> = HashMap::new();
+    let mut digest_for_inode: HashMap<u64, String> = HashMap::new();
+    let mut duplicates: Vec<(u64, u64)> = vec![];
+
+    for (inode, counter) in &counters {
+        let digest = digest_file(&counter.path)?;
+
+        match canonical_inode_for_digest.get(&digest) {
+            Some(&canonical_inode) => duplicates.push((*inode, canonical_inode)),
+            None => {
+                canonical_inode_for_digest.insert(digest.clone(), *inode);
+            }
+        }
+
+        digest_for_inode.insert(*inode, digest);
+    }
+
+    for (duplicate_inode, canonical_inode) in duplicates {
+        let Some(duplicate) = counters.remove(&duplicate_inode) else {
+            continue;
+        };
+
+        if let Some(canonical) = counters.get_mut(&canonical_inode) {
+            canonical.add_path_other_link(duplicate.path);
+            for link in duplicate.paths_other_links {
+                canonical.add_path_other_link(link);
+            }
+        }
+
+        digest_for_inode.remove(&duplicate_inode);
+    }
+
+    Ok((counters, digest_for_inode))
+}
+
+/// The path a file with `digest` lives at inside a content-addressed
+/// `store_root`, sharded by its first two hex characters.
+pub(crate) fn object_path(store_root: &str, digest: &str) -> String {
+    let prefix = &digest[..2.min(digest.len())];
+    format!("{store_root}/{prefix}/{digest}")
+}
+
+/// Stores `counter`'s content at its content-addressed location under
+/// `store_root`, skipping the copy entirely when an object with the same
+/// digest is already there, then symlinks `counter.path` and every discovered
+/// link within `limit` (all of them when `limit` is `None`) at it.
+pub(crate) fn store_counter(
+    counter: &SourceCounter,
+    digest: &str,
+    store_root: &str,
+    journal: &mut Journal,
+    verify: bool,
+    limit: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let object = object_path(store_root, digest);
+    fs::create_dir_all(
+        std::path::Path::new(&object)
+            .parent()
+            .expect("object path always has a shard directory parent"),
+    )?;
+
+    let effective_links: Vec<String> = counter
+        .paths_other_links
+        .iter()
+        .filter(|path| limit.is_none_or(|limit| is_within_limit(path, limit)))
+        .cloned()
+        .collect();
+
+    journal.begin(counter, &object, &effective_links)?;
+
+    if fs::metadata(&object).is_err() {
+        copy_atomically(counter.path.as_str(), &object)?;
+
+        if verify {
+            let destination_digest = digest_file(&object)?;
+            if destination_digest != digest {
+                fs::remove_file(&object)?;
+                return Err(Box::new(io::Error::other(format!(
+                    "Checksum mismatch copying {} into the store, original left untouched",
+                    counter.path
+                ))));
+            }
+        }
+    }
+
+    replace_with_symlink(&object, counter.path.as_str())?;
+
+    for link in &effective_links {
+        replace_with_symlink(&object, link.as_str())?;
+    }
+
+    journal.done(counter.inode)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::MetadataExt;
+
+    /// Regression test: `--limit` must leave out-of-scope hard links as real
+    /// hard links under `--store` too.
+    #[test]
+    fn leaves_links_outside_limit_as_hard_links() {
+        let dir = std::env::temp_dir().join(format!("cpwln-store-test-{}", std::process::id()));
+        let src_dir = dir.join("src");
+        let keep_dir = src_dir.join("keep");
+        let skip_dir = src_dir.join("skip");
+        fs::create_dir_all(&keep_dir).unwrap();
+        fs::create_dir_all(&skip_dir).unwrap();
+        fs::write(src_dir.join("a.txt"), b"hello").unwrap();
+        fs::hard_link(src_dir.join("a.txt"), keep_dir.join("a_keep.txt")).unwrap();
+        fs::hard_link(src_dir.join("a.txt"), skip_dir.join("a_skip.txt")).unwrap();
+
+        let original_dir = std::env::current_dir().unwrap();
+        std::env::set_current_dir(&dir).unwrap();
+
+        let metadata = fs::metadata("src/a.txt").unwrap();
+        let mut counter =
+            SourceCounter::new("src/a.txt".to_string(), metadata.ino(), metadata.nlink() - 1);
+        counter.add_path_other_link("src/keep/a_keep.txt".to_string());
+        counter.add_path_other_link("src/skip/a_skip.txt".to_string());
+
+        let digest = digest_file(&counter.path).unwrap();
+        let mut journal = Journal::open().unwrap();
+        let result = store_counter(&counter, &digest, "dest", &mut journal, true, Some("src/keep"));
+
+        let skip_is_hard_link = fs::symlink_metadata("src/skip/a_skip.txt")
+            .is_ok_and(|metadata| !metadata.file_type().is_symlink());
+        let keep_is_symlink = fs::symlink_metadata("src/keep/a_keep.txt")
+            .is_ok_and(|metadata| metadata.file_type().is_symlink());
+
+        std::env::set_current_dir(original_dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        result.unwrap();
+        assert!(skip_is_hard_link, "link outside --limit should stay a hard link");
+        assert!(keep_is_symlink, "link inside --limit should become a symlink");
+    }
+}
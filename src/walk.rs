@@ -0,0 +1,86 @@
+use std::{error::Error, fs, os::unix::fs::MetadataExt, path::Path};
+
+use crate::{
+    counter::{INodeCounterMap, SourceCounter},
+    linker::ensure_dir,
+};
+
+/// Recursively walks `source_dir`, mirroring its structure under
+/// `destination_dir` and folding every regular file into `counters`. Files
+/// that share an inode with one already seen become additional link paths
+/// on the existing `SourceCounter` instead of a new entry.
+pub(crate) fn walk_and_count(
+    source_dir: &str,
+    destination_dir: &str,
+    mut counters: INodeCounterMap,
+    dry_run: bool,
+) -> Result<INodeCounterMap, Box<dyn Error>> {
+    if !dry_run {
+        ensure_dir(destination_dir)?;
+    }
+
+    for entry in fs::read_dir(source_dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let path = entry.path().to_string_lossy().to_string();
+        let destination_path = Path::new(destination_dir)
+            .join(entry.file_name())
+            .to_string_lossy()
+            .to_string();
+
+        if metadata.is_dir() {
+            counters = walk_and_count(&path, &destination_path, counters, dry_run)?;
+            continue;
+        }
+
+        if !metadata.is_file() {
+            continue;
+        }
+
+        let inode = metadata.ino();
+        if let Some(counter) = counters.get_mut(&inode) {
+            counter.add_path_other_link(path);
+            continue;
+        }
+
+        let mut counter = SourceCounter::new_by_stat(path, &metadata);
+        counter.destination = Some(destination_path);
+        counters.insert(inode, counter);
+    }
+
+    Ok(counters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dedups_hard_linked_file_in_nested_directory() {
+        let dir = std::env::temp_dir().join(format!("cpwln-walk-test-{}", std::process::id()));
+        let source_dir = dir.join("source");
+        let nested_dir = source_dir.join("sub");
+        fs::create_dir_all(&nested_dir).unwrap();
+        fs::write(source_dir.join("a.txt"), b"hello").unwrap();
+        fs::hard_link(source_dir.join("a.txt"), nested_dir.join("b.txt")).unwrap();
+
+        let destination_dir = dir.join("dest");
+        let result = walk_and_count(
+            &source_dir.to_string_lossy(),
+            &destination_dir.to_string_lossy(),
+            INodeCounterMap::new(),
+            false,
+        );
+        let dest_dir_created = destination_dir.is_dir();
+        let nested_dest_dir_created = destination_dir.join("sub").is_dir();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        let counters = result.unwrap();
+        assert_eq!(counters.len(), 1);
+        let counter = counters.values().next().unwrap();
+        assert!(counter.is_all_links_found());
+        assert!(dest_dir_created);
+        assert!(nested_dest_dir_created);
+    }
+}